@@ -18,12 +18,20 @@
 //! The transaction can be repeated a number of times with the `--repeat` option,
 //! to stress-test an SPI bus or device.
 //!
+//! With `--test`, a generated pattern is transmitted instead of reading from stdin,
+//! and the received bytes are verified against it.
+//! This requires a MOSI-MISO loopback jumper on the bus, and turns `--repeat`
+//! into an actual correctness check rather than just a throughput loop.
+//!
 //! The `--pre-delay` option can be used to add a delay after asserting the chip select,
 //! before transmitting the data.
 //! This can be useful to give an SPI device some time to react to the chip select.
 //! Note that this wait time is implemented by the Linux kernel,
 //! which may mean the exact delay can be a few microseconds longer than the requested value.
 //!
+//! When `--bits` is greater than 8, the hex/dec input and output are grouped into words
+//! instead of individual bytes, packed according to `--word-endian`.
+//!
 //! See `spicat --help` for a list of every available option.
 //!
 //! # Install
@@ -46,6 +54,18 @@ enum OutputFormat {
 	Raw,
 }
 
+#[derive(Debug, Copy, Clone)]
+#[derive(clap::ValueEnum)]
+enum InputFormat {
+	#[clap(name = "dec")]
+	#[clap(alias = "decimal")]
+	Decimal,
+	#[clap(name = "hex")]
+	#[clap(alias = "hexadecimal")]
+	Hexadecimal,
+	Raw,
+}
+
 #[repr(u8)]
 #[derive(Debug, Copy, Clone)]
 #[derive(clap::ValueEnum)]
@@ -100,6 +120,69 @@ impl std::fmt::Display for ChipSelect {
 	}
 }
 
+#[derive(Debug, Copy, Clone)]
+#[derive(clap::ValueEnum)]
+enum WordEndian {
+	Big,
+	Little,
+}
+
+/// The number of bytes that make up one transfer word for the given `--bits` setting.
+fn word_size(bits_per_word: u8) -> usize {
+	let bits = if bits_per_word == 0 { 8 } else { bits_per_word as usize };
+	bits.div_ceil(8)
+}
+
+/// Pack a word value into `word_size` bytes, in the given byte order.
+fn pack_word(value: u32, word_size: usize, endian: WordEndian) -> Vec<u8> {
+	let le_bytes = value.to_le_bytes();
+	let mut bytes = le_bytes[..word_size].to_vec();
+	if let WordEndian::Big = endian {
+		bytes.reverse();
+	}
+	bytes
+}
+
+/// Unpack a word value from `word_size` bytes, in the given byte order.
+fn unpack_word(bytes: &[u8], endian: WordEndian) -> u32 {
+	let mut reordered = bytes.to_vec();
+	if let WordEndian::Big = endian {
+		reordered.reverse();
+	}
+	let mut le_bytes = [0u8; 4];
+	le_bytes[..reordered.len()].copy_from_slice(&reordered);
+	u32::from_le_bytes(le_bytes)
+}
+
+#[derive(Debug, Copy, Clone)]
+#[derive(clap::ValueEnum)]
+enum TestPattern {
+	/// An incrementing counter, wrapping at 256.
+	Counter,
+	/// A fixed byte value, set with `--pattern-byte`.
+	Fixed,
+	/// A pseudo-random sequence, deterministically seeded.
+	Random,
+}
+
+/// Generate a `--test` payload of `len` bytes for the given pattern.
+fn generate_test_pattern(pattern: TestPattern, pattern_byte: u8, len: usize) -> Vec<u8> {
+	match pattern {
+		TestPattern::Counter => (0..len).map(|i| (i % 256) as u8).collect(),
+		TestPattern::Fixed => vec![pattern_byte; len],
+		TestPattern::Random => {
+			// Fixed seed: the sequence only needs to be deterministic, not cryptographically random.
+			let mut state: u32 = 0xACE1_2345;
+			(0..len).map(|_| {
+				state ^= state << 13;
+				state ^= state >> 17;
+				state ^= state << 5;
+				(state & 0xFF) as u8
+			}).collect()
+		}
+	}
+}
+
 #[derive(clap::Parser)]
 #[clap(author = "Fusion Engineering")]
 struct Options {
@@ -151,7 +234,8 @@ struct Options {
 	#[clap(default_value = "active-low")]
 	chip_select: ChipSelect,
 
-	/// Bits per word for the SPI transaction.
+	/// Bits per word for the SPI transaction. Must be at most 32, since words are packed/unpacked
+	/// through a u32 for hex/dec input and output.
 	#[clap(long = "bits")]
 	#[clap(value_enum)]
 	#[clap(value_name = "N")]
@@ -162,6 +246,295 @@ struct Options {
 	#[clap(long)]
 	#[clap(value_name = "MICROSECONDS")]
 	pre_delay: Option<u16>,
+
+	/// Number of data lines to use for the transmit phase: 1 (standard), 2 (dual) or 4 (quad).
+	#[clap(long)]
+	#[clap(value_name = "LANES")]
+	#[clap(default_value = "1")]
+	tx_lanes: u8,
+
+	/// Number of data lines to use for the receive phase: 1 (standard), 2 (dual) or 4 (quad).
+	#[clap(long)]
+	#[clap(value_name = "LANES")]
+	#[clap(default_value = "1")]
+	rx_lanes: u8,
+
+	/// Clock out words LSB-first instead of the default MSB-first.
+	#[clap(long)]
+	lsb_first: bool,
+
+	/// Run a loopback self-test instead of transferring data from stdin.
+	///
+	/// Generates a test payload, transmits it, and verifies the received bytes
+	/// against the bytes sent. Requires a MOSI-MISO loopback jumper on the bus.
+	#[clap(long)]
+	test: bool,
+
+	/// Fill pattern to use for `--test`: counter, fixed or random.
+	#[clap(long)]
+	#[clap(value_enum)]
+	#[clap(default_value = "counter")]
+	pattern: TestPattern,
+
+	/// The byte value used by `--pattern fixed`.
+	#[clap(long)]
+	#[clap(value_name = "BYTE")]
+	#[clap(default_value = "255")]
+	pattern_byte: u8,
+
+	/// Payload size in bytes for `--test`.
+	#[clap(long)]
+	#[clap(value_name = "N")]
+	#[clap(default_value = "256")]
+	test_size: usize,
+
+	/// Run a scripted multi-segment transaction read from FILE instead of transferring stdin.
+	///
+	/// Each non-empty line describes one segment as `<bytes>|<delay usecs>|<cs-change 0/1>`,
+	/// where the delay and cs-change fields default to 0 when omitted or left empty.
+	/// The bytes field is parsed according to `--in-format`, which defaults to hex for `--script`
+	/// (raw is not supported here, since a script line is text).
+	/// All segments are sent as one `transfer_multiple` call within the same chip-select window,
+	/// unless a segment's cs-change field deasserts it.
+	#[clap(long)]
+	#[clap(value_name = "FILE")]
+	script: Option<PathBuf>,
+
+	/// Clock out at least N bytes of response, even if fewer bytes were sent as a command.
+	///
+	/// The transfer length becomes `max(tx_len, N)`: the transmit buffer is zero-padded
+	/// up to that length, and the receive buffer is sized to match. This allows writing a
+	/// short command and reading a longer reply within the same transfer.
+	#[clap(long)]
+	#[clap(value_name = "N")]
+	read_count: Option<usize>,
+
+	/// Parse the input stream in the given format: raw, hex[adecimal] or dec[imal].
+	///
+	/// For hex/dec, the input is tokenized on whitespace and each token is parsed as one word
+	/// (a single byte, unless `--bits` is greater than 8).
+	/// This is the inverse of the `--format` option for output. Defaults to raw for stdin input,
+	/// and to hex for `--script` (raw cannot be used with `--script`, since a script line is text).
+	#[clap(long)]
+	#[clap(value_enum)]
+	in_format: Option<InputFormat>,
+
+	/// Byte order used to pack/unpack each transfer word when `--bits` is greater than 8.
+	#[clap(long)]
+	#[clap(value_enum)]
+	#[clap(default_value = "big")]
+	word_endian: WordEndian,
+}
+
+/// Perform one full-duplex transfer, honoring `--pre-delay` and the configured lane counts.
+fn perform_transfer(spi: &Spidev, options: &Options, tx_buf: &[u8], rx_buf: &mut [u8]) -> Result<(), String> {
+	// If we have a pre-delay, add a dummy write with delay_usecs and cs_change = 0.
+	if let Some(pre_delay) = options.pre_delay {
+		let mut transfers = [
+			SpidevTransfer::write(&[]),
+			SpidevTransfer::read_write(tx_buf, rx_buf),
+		];
+
+		transfers[0].cs_change   = 0;
+		transfers[0].delay_usecs = pre_delay;
+		transfers[0].speed_hz    = options.speed;
+		transfers[1].speed_hz    = options.speed;
+		transfers[1].tx_nbits    = options.tx_lanes;
+		transfers[1].rx_nbits    = options.rx_lanes;
+		spi.transfer_multiple(&mut transfers)
+			.map_err(|e| format!("SPI transaction failed: {}", e))
+
+	// Else just do the single transfer.
+	} else {
+		let mut transfer  = SpidevTransfer::read_write(tx_buf, rx_buf);
+		transfer.speed_hz = options.speed;
+		transfer.tx_nbits = options.tx_lanes;
+		transfer.rx_nbits = options.rx_lanes;
+		spi.transfer(&mut transfer)
+			.map_err(|e| format!("SPI transaction failed: {}", e))
+	}
+}
+
+/// Run the `--test` loopback self-test: transmit a generated pattern and verify it comes back unchanged.
+fn run_loopback_test(spi: &Spidev, options: &Options, output: &mut dyn Write) -> Result<(), String> {
+	let tx_buf = generate_test_pattern(options.pattern, options.pattern_byte, options.test_size);
+	let mut rx_buf = vec![0u8; tx_buf.len()];
+
+	let mut byte_errors = 0usize;
+	let mut first_mismatch: Option<(usize, usize)> = None;
+
+	for iteration in 0..options.repeat {
+		perform_transfer(spi, options, &tx_buf, &mut rx_buf)?;
+
+		for (i, (tx, rx)) in tx_buf.iter().zip(rx_buf.iter()).enumerate() {
+			if tx != rx {
+				byte_errors += 1;
+				if first_mismatch.is_none() {
+					first_mismatch = Some((iteration, i));
+				}
+			}
+		}
+	}
+
+	writeln!(output, "Iterations: {}", options.repeat)
+		.map_err(|e| format!("Failed to write to output stream: {}", e))?;
+	writeln!(output, "Byte errors: {}", byte_errors)
+		.map_err(|e| format!("Failed to write to output stream: {}", e))?;
+	match first_mismatch {
+		Some((iteration, offset)) => writeln!(output, "First mismatch at offset: {} (iteration {})", offset, iteration),
+		None                      => writeln!(output, "First mismatch at offset: none"),
+	}.map_err(|e| format!("Failed to write to output stream: {}", e))?;
+	writeln!(output, "Result: {}", if byte_errors == 0 { "PASS" } else { "FAIL" })
+		.map_err(|e| format!("Failed to write to output stream: {}", e))?;
+
+	Ok(())
+}
+
+/// Write `data` to `output` in the given format, grouping bytes into words of `word_size`
+/// bytes for hex/dec output when `--bits` is greater than 8.
+fn write_output(output: &mut dyn Write, format: OutputFormat, data: &[u8], word_size: usize, endian: WordEndian) -> Result<(), String> {
+	match format {
+		OutputFormat::Raw => {
+			output.write_all(data).map_err(|e| format!("Failed to write to output stream: {}", e))?;
+		},
+		OutputFormat::Hexadecimal => {
+			let hex_digits = word_size * 2;
+			for (i, word) in data.chunks(word_size).enumerate() {
+				if i != 0 {
+					write!(output, " ").map_err(|e| format!("Failed to write to output stream: {}", e))?;
+				}
+				write!(output, "{:0width$X}", unpack_word(word, endian), width = hex_digits)
+					.map_err(|e| format!("Failed to write to output stream: {}", e))?;
+			}
+			writeln!(output).map_err(|e| format!("Failed to write to output stream: {}", e))?;
+		},
+		OutputFormat::Decimal => {
+			for (i, word) in data.chunks(word_size).enumerate() {
+				if i != 0 {
+					write!(output, " ").map_err(|e| format!("Failed to write to output stream: {}", e))?;
+				}
+				write!(output, "{}", unpack_word(word, endian)).map_err(|e| format!("Failed to write to output stream: {}", e))?;
+			}
+			writeln!(output).map_err(|e| format!("Failed to write to output stream: {}", e))?;
+		},
+	}
+	Ok(())
+}
+
+/// Parse whitespace-separated hex or decimal word tokens, the inverse of the hex/dec output formats.
+/// Each token is parsed as a single word value and packed into `word_size` bytes.
+fn parse_word_tokens(format: InputFormat, text: &str, word_size: usize, endian: WordEndian) -> Result<Vec<u8>, String> {
+	if let InputFormat::Raw = format {
+		return Err("--in-format raw cannot be used with --script; pass hex or dec instead".to_string());
+	}
+
+	let max_value: u32 = if word_size >= 4 { u32::MAX } else { (1u32 << (word_size * 8)) - 1 };
+	let mut bytes = Vec::new();
+	for token in text.split_whitespace() {
+		let value: u32 = match format {
+			InputFormat::Hexadecimal => u32::from_str_radix(token, 16)
+				.map_err(|_| format!("Invalid hexadecimal word: {:?}", token))?,
+			InputFormat::Decimal => token.parse()
+				.map_err(|_| format!("Invalid decimal word: {:?}", token))?,
+			InputFormat::Raw => unreachable!("handled above"),
+		};
+
+		if value > max_value {
+			return Err(format!("Value {} ({:?}) does not fit in a {}-byte word", value, token, word_size));
+		}
+		bytes.extend(pack_word(value, word_size, endian));
+	}
+	Ok(bytes)
+}
+
+/// Parse the input stream according to `--in-format`, grouping hex/dec tokens into words
+/// of `word_size` bytes when `--bits` is greater than 8. Defaults to raw when `--in-format`
+/// was not given.
+fn parse_input(format: Option<InputFormat>, raw: Vec<u8>, word_size: usize, endian: WordEndian) -> Result<Vec<u8>, String> {
+	match format.unwrap_or(InputFormat::Raw) {
+		InputFormat::Raw => Ok(raw),
+		format => parse_word_tokens(format, &String::from_utf8_lossy(&raw), word_size, endian),
+	}
+}
+
+/// One segment of a `--script` transaction: the bytes to send, how long to
+/// wait afterwards, and whether the chip select should be deasserted before the next segment.
+struct ScriptSegment {
+	data: Vec<u8>,
+	delay_usecs: u16,
+	cs_change: bool,
+}
+
+/// Parse one `--script` line: `<bytes, in --in-format>|<delay usecs>|<cs-change 0/1>`.
+/// The delay and cs-change fields default to `0` when omitted or left empty.
+fn parse_script_line(in_format: InputFormat, word_size: usize, endian: WordEndian, line: &str, line_no: usize) -> Result<ScriptSegment, String> {
+	let mut fields = line.splitn(3, '|');
+	let data_field  = fields.next().unwrap_or("").trim();
+	let delay_field = fields.next().unwrap_or("").trim();
+	let delay_field = if delay_field.is_empty() { "0" } else { delay_field };
+	let cs_field    = fields.next().unwrap_or("").trim();
+	let cs_field    = if cs_field.is_empty() { "0" } else { cs_field };
+
+	let data = parse_word_tokens(in_format, data_field, word_size, endian)
+		.map_err(|e| format!("Line {}: {}", line_no, e))?;
+
+	let delay_usecs: u16 = delay_field.parse()
+		.map_err(|_| format!("Line {}: invalid delay value {:?}", line_no, delay_field))?;
+
+	let cs_change = match cs_field {
+		"0" => false,
+		"1" => true,
+		other => return Err(format!("Line {}: invalid cs-change value {:?} (expected 0 or 1)", line_no, other)),
+	};
+
+	Ok(ScriptSegment { data, delay_usecs, cs_change })
+}
+
+/// Run a `--script` transaction: each segment is transferred in one `transfer_multiple`
+/// call within a single chip-select window, unless a segment's cs-change field deasserts it.
+fn run_script(spi: &Spidev, options: &Options, script_path: &Path, output: &mut dyn Write, format: OutputFormat, word_size: usize) -> Result<(), String> {
+	let contents = std::fs::read_to_string(script_path)
+		.map_err(|e| format!("Failed to read script file {}: {}", script_path.display(), e))?;
+
+	let in_format = options.in_format.unwrap_or(InputFormat::Hexadecimal);
+	let segments = contents.lines()
+		.enumerate()
+		.filter(|(_, line)| !line.trim().is_empty())
+		.map(|(i, line)| parse_script_line(in_format, word_size, options.word_endian, line, i + 1))
+		.collect::<Result<Vec<ScriptSegment>, String>>()?;
+
+	let mut rx_bufs: Vec<Vec<u8>> = segments.iter().map(|segment| vec![0u8; segment.data.len()]).collect();
+
+	let mut transfers: Vec<SpidevTransfer> = segments.iter().zip(rx_bufs.iter_mut())
+		.map(|(segment, rx_buf)| {
+			let mut transfer = SpidevTransfer::read_write(&segment.data, rx_buf);
+			transfer.speed_hz    = options.speed;
+			transfer.delay_usecs = segment.delay_usecs;
+			transfer.cs_change   = segment.cs_change as u8;
+			transfer.tx_nbits    = options.tx_lanes;
+			transfer.rx_nbits    = options.rx_lanes;
+			transfer
+		})
+		.collect();
+
+	spi.transfer_multiple(&mut transfers)
+		.map_err(|e| format!("SPI transaction failed: {}", e))?;
+
+	for rx_buf in &rx_bufs {
+		write_output(output, format, rx_buf, word_size, options.word_endian)?;
+	}
+
+	Ok(())
+}
+
+/// Map a lane count (1, 2 or 4) to the SPI_{TX,RX}_{DUAL,QUAD} flag that enables it.
+fn lane_mode_flags(flag_name: &str, lanes: u8, dual: SpiModeFlags, quad: SpiModeFlags) -> Result<SpiModeFlags, String> {
+	match lanes {
+		1 => Ok(SpiModeFlags::empty()),
+		2 => Ok(dual),
+		4 => Ok(quad),
+		other => Err(format!("Invalid {} value {}: only 1, 2 or 4 lanes are supported", flag_name, other)),
+	}
 }
 
 fn main() {
@@ -176,25 +549,28 @@ fn do_main(options: Options) -> Result<(), String> {
 	let stdin  = std::io::stdin();
 	let stdout = std::io::stdout();
 
+	// Validate lane counts and word size before touching the device, so a bad option value
+	// is reported without side effects.
+	let tx_lane_flags = lane_mode_flags("--tx-lanes", options.tx_lanes, SpiModeFlags::SPI_TX_DUAL, SpiModeFlags::SPI_TX_QUAD)?;
+	let rx_lane_flags = lane_mode_flags("--rx-lanes", options.rx_lanes, SpiModeFlags::SPI_RX_DUAL, SpiModeFlags::SPI_RX_QUAD)?;
+	if options.bits_per_word > 32 {
+		return Err(format!("--bits {} is not supported: words are packed into a u32, so --bits can be at most 32", options.bits_per_word));
+	}
+
 	let mut spi = Spidev::open(&options.spidev)
 		.map_err(|e| format!("Failed to open spidev {}: {}", options.spidev.display(), e))?;
 	spi.configure(&SpidevOptions::new().bits_per_word(options.bits_per_word).build())
 		.map_err(|e| format!("Failed to set {} bits per word: {}", options.bits_per_word, e))?;
 	spi.configure(&SpidevOptions::new().max_speed_hz(options.speed).build())
 		.map_err(|e| format!("Failed to max speed to {} Hz: {}", options.speed, e))?;
-	spi.configure(&SpidevOptions::new().mode(options.mode.flags()).build())
-		.map_err(|e| format!("Failed to set SPI mode to {}: {}", options.mode as u8, e))?;
-	spi.configure(&SpidevOptions::new().mode(options.chip_select.flags()).build())
-		.map_err(|e| format!("Failed to set chip select mode to {}: {}", options.chip_select, e))?;
 
-
-	let mut input : Box<dyn Read> = if options.input == Path::new("-") {
-		Box::new(stdin.lock())
-	} else {
-		Box::new(std::fs::File::open(&options.input)
-			.map_err(|e| format!("Failed to open input file {}: {}", options.input.display(), e))?
-		)
-	};
+	// The mode register is written as a whole, so all contributing flags have to be combined
+	// into a single configure() call rather than applied one option at a time.
+	let mode_flags = options.mode.flags() | options.chip_select.flags() | tx_lane_flags | rx_lane_flags;
+	spi.configure(&SpidevOptions::new().mode(mode_flags).build())
+		.map_err(|e| format!("Failed to set SPI mode flags: {}", e))?;
+	spi.configure(&SpidevOptions::new().lsb_first(options.lsb_first).build())
+		.map_err(|e| format!("Failed to set bit order to {}: {}", if options.lsb_first { "LSB-first" } else { "MSB-first" }, e))?;
 
 	let output_fd: i32;
 	let mut output : Box<dyn Write> = if options.output == Path::new("-") {
@@ -208,13 +584,6 @@ fn do_main(options: Options) -> Result<(), String> {
 		Box::new(file)
 	};
 
-	let mut tx_buf = Vec::new();
-	input.read_to_end(&mut tx_buf)
-		.map_err(|e| format!("Failed to read input message: {}", e))?;
-
-	let mut rx_buf = Vec::new();
-	rx_buf.resize(tx_buf.len(), 0u8);
-
 	let format = options.format.unwrap_or_else(|| {
 		if unsafe { libc::isatty(output_fd) } != 0 {
 			OutputFormat::Hexadecimal
@@ -223,55 +592,47 @@ fn do_main(options: Options) -> Result<(), String> {
 		}
 	});
 
-	for _ in 0..options.repeat {
-		// If we have a pre-delay, add a dummy write with delay_usecs and cs_change = 0.
-		if let Some(pre_delay) = options.pre_delay {
-			let mut transfers = [
-				SpidevTransfer::write(&[]),
-				SpidevTransfer::read_write(&tx_buf, &mut rx_buf),
-			];
-
-			transfers[0].cs_change   = 0;
-			transfers[0].delay_usecs = pre_delay;
-			transfers[0].speed_hz    = options.speed;
-			transfers[1].speed_hz    = options.speed;
-			spi.transfer_multiple(&mut transfers)
-				.map_err(|e| format!("SPI transaction failed: {}", e))?;
-
-		// Else just do the single transfer.
-		} else {
-			let mut transfer  = SpidevTransfer::read_write(&tx_buf, &mut rx_buf);
-			transfer.speed_hz = options.speed;
-			spi.transfer(&mut transfer)
-				.map_err(|e| format!("SPI transaction failed: {}", e))?;
-		}
+	if options.test {
+		return run_loopback_test(&spi, &options, &mut output);
+	}
 
+	let word_size = word_size(options.bits_per_word);
 
-		// Print the received data in the desired format.
-		match format {
-			OutputFormat::Raw => {
-				output.write_all(&rx_buf).map_err(|e| format!("Failed to write to output stream: {}", e))?;
-			},
-			OutputFormat::Hexadecimal => {
-				for (i, byte) in rx_buf.iter().enumerate() {
-					if i != 0 {
-						write!(output, " ").map_err(|e| format!("Failed to write to output stream: {}", e))?;
-					}
-					write!(output, "{:02X}", byte).map_err(|e| format!("Failed to write to output stream: {}", e))?;
-				}
-				writeln!(output).map_err(|e| format!("Failed to write to output stream: {}", e))?;
-			},
-			OutputFormat::Decimal => {
-				for (i, byte) in rx_buf.iter().enumerate() {
-					if i != 0 {
-						write!(output, " ").map_err(|e| format!("Failed to write to output stream: {}", e))?;
-					}
-					write!(output, "{}", byte).map_err(|e| format!("Failed to write to output stream: {}", e))?;
-				}
-				writeln!(output).map_err(|e| format!("Failed to write to output stream: {}", e))?;
-			},
+	if let Some(script_path) = &options.script {
+		return run_script(&spi, &options, script_path, &mut output, format, word_size);
+	}
+
+	let mut input : Box<dyn Read> = if options.input == Path::new("-") {
+		Box::new(stdin.lock())
+	} else {
+		Box::new(std::fs::File::open(&options.input)
+			.map_err(|e| format!("Failed to open input file {}: {}", options.input.display(), e))?
+		)
+	};
+
+	let mut raw_input = Vec::new();
+	input.read_to_end(&mut raw_input)
+		.map_err(|e| format!("Failed to read input message: {}", e))?;
+	let mut tx_buf = parse_input(options.in_format, raw_input, word_size, options.word_endian)?;
+
+	if let Some(read_count) = options.read_count {
+		if read_count > tx_buf.len() {
+			tx_buf.resize(read_count, 0u8);
 		}
 	}
 
+	// Round the transfer length up to a whole number of words.
+	if tx_buf.len() % word_size != 0 {
+		tx_buf.resize(tx_buf.len() + word_size - tx_buf.len() % word_size, 0u8);
+	}
+
+	let mut rx_buf = Vec::new();
+	rx_buf.resize(tx_buf.len(), 0u8);
+
+	for _ in 0..options.repeat {
+		perform_transfer(&spi, &options, &tx_buf, &mut rx_buf)?;
+		write_output(&mut output, format, &rx_buf, word_size, options.word_endian)?;
+	}
+
 	Ok(())
 }